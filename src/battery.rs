@@ -1,21 +1,77 @@
 use anyhow::Result;
+use std::collections::VecDeque;
 use std::fs;
+use std::path::Path;
 use std::process::Command;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, Mutex};
+use std::time::Instant;
 
-use crate::config::Config;
-use crate::types::{BatteryInfo, BatteryStatus};
+use crate::config::{Config, SystemAction};
+use crate::types::{BatteryInfo, BatteryStatus, SingleBattery};
 
 impl BatteryInfo {
+    /// Reads a single battery path and wraps it as a one-battery aggregate.
     pub fn read_from_sysfs(battery_path: &str) -> Result<Self> {
-        let capacity_path = format!("{}/capacity", battery_path);
-        let status_path = format!("{}/status", battery_path);
+        Self::read_from_paths(&[battery_path.to_string()])
+    }
+
+    /// Reads and aggregates every battery path in `paths`. A pack that
+    /// fails to read (e.g. briefly missing mid-hotplug) is skipped rather
+    /// than failing the whole read, as long as at least one pack succeeds.
+    pub fn read_from_paths(paths: &[String]) -> Result<Self> {
+        let mut batteries = Vec::new();
+        for path in paths {
+            match Self::read_single(path) {
+                Ok(battery) => batteries.push(battery),
+                Err(e) => eprintln!("Failed to read battery at {}: {}", path, e),
+            }
+        }
+
+        if batteries.is_empty() {
+            return Err(anyhow::anyhow!("No readable batteries found in {:?}", paths));
+        }
+
+        Ok(Self::aggregate(batteries))
+    }
 
-        let capacity_str = fs::read_to_string(&capacity_path)
-            .map_err(|e| anyhow::anyhow!("Failed to read capacity from {}: {}", capacity_path, e))?;
-        let capacity = capacity_str.trim().parse::<f64>()
-            .map_err(|e| anyhow::anyhow!("Failed to parse capacity: {}", e))?;
+    /// Combines per-battery readings into a single weighted view: `capacity`
+    /// is weighted by each pack's `energy_full` so a nearly-empty small cell
+    /// doesn't distort the total, and `status` folds multiple packs the way
+    /// waybar does (`Charging` if any pack is charging, `Discharging` only
+    /// if none are, `Full` only if every pack reports `Full`).
+    fn aggregate(batteries: Vec<SingleBattery>) -> Self {
+        let total_weight: f64 = batteries.iter().map(|b| b.energy_full).sum();
+        let capacity = if total_weight > 0.0 {
+            batteries.iter().map(|b| b.capacity * b.energy_full).sum::<f64>() / total_weight
+        } else {
+            batteries.iter().map(|b| b.capacity).sum::<f64>() / batteries.len() as f64
+        };
 
+        let status = if batteries.iter().any(|b| b.status == BatteryStatus::Charging) {
+            BatteryStatus::Charging
+        } else if batteries.iter().any(|b| b.status == BatteryStatus::Discharging) {
+            BatteryStatus::Discharging
+        } else if batteries.iter().all(|b| b.status == BatteryStatus::Full) {
+            BatteryStatus::Full
+        } else {
+            BatteryStatus::Unknown
+        };
+
+        let energy_now: f64 = batteries.iter().map(|b| b.energy_now).sum();
+        let energy_full: f64 = batteries.iter().map(|b| b.energy_full).sum();
+        let power_now: f64 = batteries.iter().map(|b| b.power_now).sum();
+
+        Self { capacity, status, energy_now, energy_full, power_now, batteries }
+    }
+
+    fn read_single(battery_path: &str) -> Result<SingleBattery> {
+        let name = Path::new(battery_path)
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_else(|| battery_path.to_string());
+
+        let status_path = format!("{}/status", battery_path);
         let status_str = fs::read_to_string(&status_path)
             .map_err(|e| anyhow::anyhow!("Failed to read status from {}: {}", status_path, e))?;
         let status = match status_str.trim() {
@@ -25,14 +81,66 @@ impl BatteryInfo {
             _ => BatteryStatus::Unknown,
         };
 
-        Ok(Self { capacity, status })
+        let (capacity, energy_full, energy_now, power_now) =
+            if let Some(v) = Self::read_energy_triplet(battery_path, "energy_now", "energy_full", "power_now") {
+                v
+            } else if let Some(v) = Self::read_energy_triplet(battery_path, "charge_now", "charge_full", "current_now") {
+                v
+            } else {
+                let capacity_path = format!("{}/capacity", battery_path);
+                let capacity_str = fs::read_to_string(&capacity_path)
+                    .map_err(|e| anyhow::anyhow!("Failed to read capacity from {}: {}", capacity_path, e))?;
+                let capacity = capacity_str.trim().parse::<f64>()
+                    .map_err(|e| anyhow::anyhow!("Failed to parse capacity: {}", e))?;
+                (capacity, 1.0, 0.0, 0.0)
+            };
+
+        Ok(SingleBattery { name, capacity, status, energy_full, energy_now, power_now })
+    }
+
+    /// Returns `(percent, full_capacity, now_capacity, rate)`, reading
+    /// `now_file`/`full_file` for the percentage and weight, and `rate_file`
+    /// (`power_now` or `current_now`) for the instantaneous rate used by
+    /// time-to-empty/time-to-full estimates. `None` if either required node
+    /// is missing or unparsable; the rate node alone is allowed to be
+    /// missing (some drivers omit it), in which case rate is `0.0`.
+    fn read_energy_triplet(
+        battery_path: &str,
+        now_file: &str,
+        full_file: &str,
+        rate_file: &str,
+    ) -> Option<(f64, f64, f64, f64)> {
+        let now = Self::read_sysfs_f64(battery_path, now_file)?;
+        let full = Self::read_sysfs_f64(battery_path, full_file)?;
+        if full <= 0.0 {
+            return None;
+        }
+        let rate = Self::read_sysfs_f64(battery_path, rate_file).unwrap_or(0.0);
+        Some((now / full * 100.0, full, now, rate))
+    }
+
+    fn read_sysfs_f64(battery_path: &str, file: &str) -> Option<f64> {
+        fs::read_to_string(format!("{}/{}", battery_path, file)).ok()?
+            .trim().parse::<f64>().ok()
     }
 }
 
+/// How many instantaneous-rate samples to keep for median smoothing of the
+/// time-to-empty/time-to-full estimate. `power_now`/`current_now` is noisy
+/// from one poll to the next, so a raw sample makes the displayed estimate
+/// jump around.
+const RATE_SAMPLE_WINDOW: usize = 5;
+
+#[derive(Clone)]
 pub struct BatteryMonitor {
     config: Config,
     last_state: Arc<Mutex<Option<BatteryInfo>>>,
     last_healthy_notified: Arc<Mutex<bool>>,
+    charge_limit_warned: Arc<Mutex<bool>>,
+    rate_samples: Arc<Mutex<VecDeque<f64>>>,
+    emergency_deadline: Arc<Mutex<Option<Instant>>>,
+    emergency_fired: Arc<Mutex<bool>>,
+    countdown_active: Arc<AtomicBool>,
 }
 
 impl BatteryMonitor {
@@ -41,6 +149,181 @@ impl BatteryMonitor {
             config,
             last_state: Arc::new(Mutex::new(None)),
             last_healthy_notified: Arc::new(Mutex::new(false)),
+            charge_limit_warned: Arc::new(Mutex::new(false)),
+            rate_samples: Arc::new(Mutex::new(VecDeque::with_capacity(RATE_SAMPLE_WINDOW))),
+            emergency_deadline: Arc::new(Mutex::new(None)),
+            emergency_fired: Arc::new(Mutex::new(false)),
+            countdown_active: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    /// Claims the right to run the once-a-second emergency countdown
+    /// ticker. Returns `false` if one is already running (e.g. the
+    /// inotify watch and the poll-interval fallback both observed the
+    /// "emergency" level before the first ticker finished), so callers
+    /// must not start a second concurrent ticker.
+    pub fn try_start_countdown(&self) -> bool {
+        self.countdown_active
+            .compare_exchange(false, true, Ordering::SeqCst, Ordering::SeqCst)
+            .is_ok()
+    }
+
+    /// Releases the countdown-ticker claim once it stops (cancelled,
+    /// fired, or errored), allowing a future emergency to start a new one.
+    pub fn end_countdown(&self) {
+        self.countdown_active.store(false, Ordering::SeqCst);
+    }
+
+    /// Disarms the `critical_action` ladder once AC is reconnected, so a
+    /// later discharge cycle starts a fresh countdown instead of treating
+    /// the emergency as already fired. A no-op while still discharging.
+    fn reset_emergency_state_if_plugged(&self, status: &BatteryStatus) {
+        if matches!(status, BatteryStatus::Charging | BatteryStatus::Full) {
+            *self.emergency_deadline.lock().unwrap() = None;
+            *self.emergency_fired.lock().unwrap() = false;
+        }
+    }
+
+    /// Runs the `[critical_action]` escalation ladder: once capacity drops
+    /// to `emergency_threshold` while discharging, counts down
+    /// `countdown_secs` and then fires the configured system action. Armed
+    /// only once per discharge cycle (reset when AC is reconnected, see
+    /// `reset_emergency_state_if_plugged`), and a no-op entirely when
+    /// `action` is unset.
+    ///
+    /// Messages and the icon go through the same `format`/`format_icons`
+    /// machinery as the other levels, and the whole ladder respects
+    /// `disable = ["emergency"]` and an empty `format.emergency_countdown`
+    /// the same way any other level would.
+    fn check_emergency_action(&self, battery_info: &BatteryInfo) -> Option<(String, String, String, u64)> {
+        let action = self.config.critical_action.action.as_ref()?;
+
+        if battery_info.status != BatteryStatus::Discharging
+            || battery_info.capacity > self.config.critical_action.emergency_threshold
+        {
+            return None;
+        }
+
+        if self.is_disabled("emergency") || self.format_for_level("emergency").trim().is_empty() {
+            return None;
+        }
+
+        if *self.emergency_fired.lock().unwrap() {
+            return None;
+        }
+
+        let now = Instant::now();
+        let mut deadline = self.emergency_deadline.lock().unwrap();
+        let deadline_at = *deadline.get_or_insert_with(|| {
+            now + std::time::Duration::from_secs(self.config.critical_action.countdown_secs)
+        });
+        let remaining = deadline_at.saturating_duration_since(now);
+        drop(deadline);
+
+        let capacity = battery_info.capacity as i32;
+        let icon = self.resolve_icon(battery_info.capacity, &battery_info.status);
+
+        if remaining.is_zero() {
+            *self.emergency_fired.lock().unwrap() = true;
+            self.execute_command(&Some(action.systemctl_command().to_string()));
+            let message = render_format(
+                &self.config.format.emergency_fired,
+                capacity,
+                &battery_info.status,
+                "",
+                &icon,
+                action.as_str(),
+            );
+            return Some((icon, message, "emergency".to_string(), 0));
+        }
+
+        let message = render_format(
+            &self.config.format.emergency_countdown,
+            capacity,
+            &battery_info.status,
+            &remaining.as_secs().to_string(),
+            &icon,
+            action.as_str(),
+        );
+
+        Some((icon, message, "emergency".to_string(), 0))
+    }
+
+    /// Estimates remaining time (discharging) or time-to-full (charging)
+    /// from the median of the last few `power_now`/`current_now` samples.
+    /// Returns `None` when the rate is zero or unavailable, or the battery
+    /// is neither charging nor discharging.
+    fn estimate_time_remaining(&self, info: &BatteryInfo) -> Option<std::time::Duration> {
+        if info.power_now <= 0.0 {
+            return None;
+        }
+
+        let median_rate = {
+            let mut samples = self.rate_samples.lock().unwrap();
+            samples.push_back(info.power_now);
+            if samples.len() > RATE_SAMPLE_WINDOW {
+                samples.pop_front();
+            }
+            median(samples.iter().copied().collect())
+        };
+
+        if median_rate <= 0.0 {
+            return None;
+        }
+
+        let hours = match info.status {
+            BatteryStatus::Discharging => info.energy_now / median_rate,
+            BatteryStatus::Charging => (info.energy_full - info.energy_now) / median_rate,
+            BatteryStatus::Full | BatteryStatus::Unknown => return None,
+        };
+
+        Some(std::time::Duration::from_secs_f64(hours.max(0.0) * 3600.0))
+    }
+
+    /// Writes the configured charge thresholds to sysfs. Safe to call
+    /// repeatedly: some kernels reset `charge_control_*_threshold` back to
+    /// their firmware defaults after suspend, so callers re-apply this on
+    /// every tick rather than only once at startup.
+    ///
+    /// Tolerant per-pack like `read_from_paths`: on a multi-battery laptop
+    /// where only one pack exposes `charge_control_end_threshold`, the
+    /// other pack's write failure is logged but doesn't fail the whole
+    /// call — only every configured path failing does.
+    pub fn apply_charge_limit(&self) -> Result<()> {
+        if !self.config.charge_limit.enabled {
+            return Ok(());
+        }
+
+        let paths = self.config.battery_path.resolve();
+        let mut last_err = None;
+        let mut failures = 0;
+
+        for battery_path in &paths {
+            let end_path = format!("{}/charge_control_end_threshold", battery_path);
+            let start_path = format!("{}/charge_control_start_threshold", battery_path);
+
+            let end_percent = self.config.charge_limit.end_percent as i32;
+            if let Err(e) = fs::write(&end_path, end_percent.to_string()) {
+                eprintln!("Failed to write {}: {}", end_path, e);
+                failures += 1;
+                last_err = Some(anyhow::anyhow!("Failed to write {}: {}", end_path, e));
+                continue;
+            }
+
+            // Many ThinkPads/ASUS laptops only expose the end threshold;
+            // treat a missing or unwritable start node as non-fatal.
+            if Path::new(&start_path).exists() {
+                let start_percent = self.config.charge_limit.start_percent as i32;
+                if let Err(e) = fs::write(&start_path, start_percent.to_string()) {
+                    eprintln!("Failed to write {}: {}", start_path, e);
+                }
+            }
+        }
+
+        if failures > 0 && failures == paths.len() {
+            Err(last_err.unwrap())
+        } else {
+            Ok(())
         }
     }
 
@@ -68,15 +351,101 @@ impl BatteryMonitor {
         })
     }
 
+    /// Fires `on_plugged`/`on_unplugged` the instant the power source itself
+    /// transitions, independent of which capacity band the battery is in.
+    /// `Charging` and `Full` both count as plugged in.
+    fn handle_plug_transition(&self, last_status: &BatteryStatus, current_status: &BatteryStatus) {
+        let was_plugged = is_plugged(last_status);
+        let is_plugged_now = is_plugged(current_status);
+
+        if is_plugged_now && !was_plugged {
+            self.execute_command(&self.config.commands.on_plugged);
+        } else if !is_plugged_now && was_plugged {
+            self.execute_command(&self.config.commands.on_unplugged);
+        }
+    }
+
+    fn format_for_level(&self, level: &str) -> &str {
+        match level {
+            "healthy" => &self.config.format.healthy,
+            "charging" => &self.config.format.charging,
+            "critical" => &self.config.format.critical,
+            "low" => &self.config.format.low,
+            "normal" => &self.config.format.normal,
+            "full" => &self.config.format.full,
+            "charge_limited" => &self.config.format.charge_limited,
+            "emergency" => &self.config.format.emergency_countdown,
+            "warning" => &self.config.format.charge_limit_warning,
+            _ => "",
+        }
+    }
+
+    /// Buckets `capacity` evenly across `format_icons` and appends the
+    /// `-charging` infix used by standard symbolic battery icon themes
+    /// while the battery is charging.
+    fn resolve_icon(&self, capacity: f64, status: &BatteryStatus) -> String {
+        let icons = &self.config.format_icons;
+        if icons.is_empty() {
+            return "battery-missing-symbolic".to_string();
+        }
+
+        let clamped = capacity.clamp(0.0, 100.0);
+        let idx = ((clamped / 100.0) * (icons.len() - 1) as f64).round() as usize;
+        let base = &icons[idx.min(icons.len() - 1)];
+
+        if *status == BatteryStatus::Charging {
+            base.replacen("-symbolic", "-charging-symbolic", 1)
+        } else {
+            base.clone()
+        }
+    }
+
     pub fn check_battery(&self) -> Result<Option<(String, String, String, u64)>> {
-        let battery_info = BatteryInfo::read_from_sysfs(&self.config.battery_path)?;
-        
+        let paths = self.config.battery_path.resolve();
+        let battery_info = BatteryInfo::read_from_paths(&paths)?;
+
+        if let Err(e) = self.apply_charge_limit() {
+            let mut warned = self.charge_limit_warned.lock().unwrap();
+            if !*warned {
+                *warned = true;
+                eprintln!("{}", e);
+
+                if !self.is_disabled("warning") {
+                    let template = self.format_for_level("warning");
+                    if !template.trim().is_empty() {
+                        let icon = "dialog-warning-symbolic";
+                        let message = render_format(template, battery_info.capacity as i32, &battery_info.status, "", icon, "");
+                        return Ok(Some((icon.to_string(), message, "warning".to_string(), self.config.timeouts.critical)));
+                    }
+                }
+            }
+        }
+
+        self.reset_emergency_state_if_plugged(&battery_info.status);
+        if !matches!(battery_info.status, BatteryStatus::Charging | BatteryStatus::Full) {
+            if let Some(result) = self.check_emergency_action(&battery_info) {
+                *self.last_state.lock().unwrap() = Some(battery_info.clone());
+                return Ok(Some(result));
+            }
+        }
+
+        // Sampled unconditionally so the median smooths consecutive polls
+        // rather than mixing readings from unrelated, far-apart crossings
+        // (should_show below only gates *display* of the estimate).
+        let time_estimate = if self.config.show_time_estimate {
+            self.estimate_time_remaining(&battery_info)
+        } else {
+            None
+        };
+
         let mut last = self.last_state.lock().unwrap();
         let mut last_healthy = self.last_healthy_notified.lock().unwrap();
-        
+
         let should_show = if let Some(ref last_info) = *last {
+            self.handle_plug_transition(&last_info.status, &battery_info.status);
+
             let state_changed = last_info.status != battery_info.status;
-            
+
             let crossing_threshold = battery_info.status == BatteryStatus::Discharging && 
                 ((battery_info.capacity <= self.config.critical_threshold && last_info.capacity > self.config.critical_threshold) ||
                  (battery_info.capacity <= self.config.low_threshold && last_info.capacity > self.config.low_threshold));
@@ -85,16 +454,21 @@ impl BatteryMonitor {
                 battery_info.capacity >= self.config.healthy_threshold &&
                 last_info.capacity < self.config.healthy_threshold &&
                 !*last_healthy;
-            
+
+            let crossing_charge_limited = self.config.charge_limit.enabled &&
+                battery_info.status == BatteryStatus::Charging &&
+                battery_info.capacity >= self.config.charge_limit.end_percent &&
+                last_info.capacity < self.config.charge_limit.end_percent;
+
             if battery_info.status == BatteryStatus::Discharging {
                 *last_healthy = false;
             }
-            
+
             if crossing_healthy {
                 *last_healthy = true;
             }
-            
-            state_changed || crossing_threshold || crossing_healthy
+
+            state_changed || crossing_threshold || crossing_healthy || crossing_charge_limited
         } else {
             true
         };
@@ -103,69 +477,288 @@ impl BatteryMonitor {
 
         if should_show {
             let capacity = battery_info.capacity as i32;
-            let (icon, message, level, command, timeout) = match battery_info.status {
+            let (level, command, mut timeout): (&str, &Option<String>, u64) = match battery_info.status {
                 BatteryStatus::Charging => {
-                    if battery_info.capacity >= self.config.healthy_threshold {
-                        ("battery-good-charging-symbolic", 
-                         format!("Healthy {}%", capacity), 
-                         "healthy", 
-                         &self.config.commands.on_healthy,
-                         self.config.timeouts.healthy)
+                    if self.config.charge_limit.enabled
+                        && battery_info.capacity >= self.config.charge_limit.end_percent {
+                        ("charge_limited", &self.config.commands.on_charge_limited, self.config.timeouts.charge_limited)
+                    } else if battery_info.capacity >= self.config.healthy_threshold {
+                        ("healthy", &self.config.commands.on_healthy, self.config.timeouts.healthy)
                     } else {
-                        ("battery-level-50-charging-symbolic", 
-                         format!("Charging {}%", capacity), 
-                         "charging", 
-                         &self.config.commands.on_charging,
-                         self.config.timeouts.charging)
+                        ("charging", &self.config.commands.on_charging, self.config.timeouts.charging)
                     }
                 }
                 BatteryStatus::Discharging => {
                     if battery_info.capacity <= self.config.critical_threshold {
-                        ("battery-level-10-symbolic",
-                         format!("Critical {}%", capacity),
-                         "critical", 
-                         &self.config.commands.on_critical,
-                         self.config.timeouts.critical)
+                        ("critical", &self.config.commands.on_critical, self.config.timeouts.critical)
                     } else if battery_info.capacity <= self.config.low_threshold {
-                        ("battery-level-20-symbolic",
-                         format!("Low {}%", capacity),
-                         "low", 
-                         &self.config.commands.on_low,
-                         self.config.timeouts.low)
+                        ("low", &self.config.commands.on_low, self.config.timeouts.low)
                     } else {
-                        ("battery-good-symbolic",
-                         format!("Discharging {}%", capacity),
-                         "normal", 
-                         &self.config.commands.on_discharging,
-                         self.config.timeouts.discharging)
+                        ("normal", &self.config.commands.on_discharging, self.config.timeouts.discharging)
                     }
                 }
-                BatteryStatus::Full => {
-                    ("battery-full-symbolic",
-                     format!("Full {}%", capacity),
-                     "full", 
-                     &self.config.commands.on_full,
-                     self.config.timeouts.full)
-                }
-                BatteryStatus::Unknown => {
-                    ("battery-missing-symbolic",
-                     format!("Battery {}%", capacity),
-                     "normal", 
-                     &None,
-                     self.config.timeouts.discharging)
-                }
+                BatteryStatus::Full => ("full", &self.config.commands.on_full, self.config.timeouts.full),
+                BatteryStatus::Unknown => ("normal", &None, self.config.timeouts.discharging),
             };
 
+            // Part of the critical_action ladder: once armed, the critical
+            // OSD stays on screen instead of timing out, escalating the
+            // warning ahead of the emergency countdown.
+            if level == "critical" && self.config.critical_action.action.is_some() {
+                timeout = 0;
+            }
+
             // Check if this notification is disabled
             if self.is_disabled(level) {
                 return Ok(None);
             }
 
+            let template = self.format_for_level(level);
+            if template.trim().is_empty() {
+                return Ok(None);
+            }
+
+            let icon = self.resolve_icon(battery_info.capacity, &battery_info.status);
+
+            let time_str = time_estimate.map(format_duration).unwrap_or_default();
+
+            let mut message = render_format(template, capacity, &battery_info.status, &time_str, &icon, "");
+
+            if self.config.show_battery_detail && battery_info.batteries.len() > 1 {
+                let detail = battery_info.batteries.iter()
+                    .map(|b| format!("{}: {}%", b.name, b.capacity as i32))
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                message = format!("{} ({})", message, detail);
+            }
+
+            if self.config.show_time_estimate && !time_str.is_empty() && !template.contains("{time}") {
+                message = format!("{} \u{b7} {} left", message, time_str);
+            }
+
             self.execute_command(command);
 
-            return Ok(Some((icon.to_string(), message, level.to_string(), timeout)));
+            return Ok(Some((icon, message, level.to_string(), timeout)));
         }
 
         Ok(None)
     }
 }
+
+fn is_plugged(status: &BatteryStatus) -> bool {
+    matches!(status, BatteryStatus::Charging | BatteryStatus::Full)
+}
+
+fn render_format(template: &str, capacity: i32, status: &BatteryStatus, time: &str, icon: &str, action: &str) -> String {
+    let status_str = match status {
+        BatteryStatus::Charging => "Charging",
+        BatteryStatus::Discharging => "Discharging",
+        BatteryStatus::Full => "Full",
+        BatteryStatus::Unknown => "Unknown",
+    };
+
+    template
+        .replace("{capacity}", &capacity.to_string())
+        .replace("{status}", status_str)
+        .replace("{time}", time)
+        .replace("{icon}", icon)
+        .replace("{action}", action)
+}
+
+fn median(mut values: Vec<f64>) -> f64 {
+    if values.is_empty() {
+        return 0.0;
+    }
+    values.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    let mid = values.len() / 2;
+    if values.len() % 2 == 0 {
+        (values[mid - 1] + values[mid]) / 2.0
+    } else {
+        values[mid]
+    }
+}
+
+fn format_duration(duration: std::time::Duration) -> String {
+    let total_minutes = (duration.as_secs_f64() / 60.0).round() as u64;
+    format!("{}:{:02}", total_minutes / 60, total_minutes % 60)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn single_battery(name: &str, capacity: f64, status: BatteryStatus, energy_full: f64, energy_now: f64) -> SingleBattery {
+        SingleBattery { name: name.to_string(), capacity, status, energy_full, energy_now, power_now: 0.0 }
+    }
+
+    #[test]
+    fn median_of_odd_sample_count() {
+        assert_eq!(median(vec![3.0, 1.0, 2.0]), 2.0);
+    }
+
+    #[test]
+    fn median_of_even_sample_count() {
+        assert_eq!(median(vec![1.0, 2.0, 3.0, 4.0]), 2.5);
+    }
+
+    #[test]
+    fn median_of_empty_is_zero() {
+        assert_eq!(median(vec![]), 0.0);
+    }
+
+    #[test]
+    fn format_duration_pads_minutes() {
+        assert_eq!(format_duration(std::time::Duration::from_secs(65 * 60)), "1:05");
+    }
+
+    #[test]
+    fn aggregate_weights_capacity_by_energy_full() {
+        // A nearly-empty small cell (10% of a 10Wh pack) shouldn't drag a
+        // nearly-full large pack (90% of a 90Wh pack) down to the 50%
+        // unweighted average.
+        let batteries = vec![
+            single_battery("BAT0", 90.0, BatteryStatus::Discharging, 90.0, 81.0),
+            single_battery("BAT1", 10.0, BatteryStatus::Discharging, 10.0, 1.0),
+        ];
+        let info = BatteryInfo::aggregate(batteries);
+        assert_eq!(info.capacity, 82.0);
+    }
+
+    #[test]
+    fn aggregate_falls_back_to_unweighted_average_without_energy_full() {
+        let batteries = vec![
+            single_battery("BAT0", 40.0, BatteryStatus::Discharging, 0.0, 0.0),
+            single_battery("BAT1", 60.0, BatteryStatus::Discharging, 0.0, 0.0),
+        ];
+        let info = BatteryInfo::aggregate(batteries);
+        assert_eq!(info.capacity, 50.0);
+    }
+
+    #[test]
+    fn aggregate_status_charging_if_any_pack_charging() {
+        let batteries = vec![
+            single_battery("BAT0", 50.0, BatteryStatus::Discharging, 50.0, 25.0),
+            single_battery("BAT1", 50.0, BatteryStatus::Charging, 50.0, 25.0),
+        ];
+        let info = BatteryInfo::aggregate(batteries);
+        assert_eq!(info.status, BatteryStatus::Charging);
+    }
+
+    #[test]
+    fn aggregate_status_full_only_if_all_packs_full() {
+        let batteries = vec![
+            single_battery("BAT0", 100.0, BatteryStatus::Full, 50.0, 50.0),
+            single_battery("BAT1", 80.0, BatteryStatus::Discharging, 50.0, 40.0),
+        ];
+        let info = BatteryInfo::aggregate(batteries);
+        assert_eq!(info.status, BatteryStatus::Discharging);
+    }
+
+    #[test]
+    fn render_format_replaces_all_placeholders() {
+        let out = render_format(
+            "{status} {capacity}% {icon} {time} {action}",
+            42,
+            &BatteryStatus::Discharging,
+            "1:30",
+            "battery-level-40-symbolic",
+            "suspend",
+        );
+        assert_eq!(out, "Discharging 42% battery-level-40-symbolic 1:30 suspend");
+    }
+
+    #[test]
+    fn resolve_icon_buckets_capacity_evenly() {
+        let monitor = BatteryMonitor::new(Config::default());
+        assert_eq!(monitor.resolve_icon(0.0, &BatteryStatus::Discharging), "battery-level-0-symbolic");
+        assert_eq!(monitor.resolve_icon(100.0, &BatteryStatus::Discharging), "battery-level-100-symbolic");
+        assert_eq!(monitor.resolve_icon(55.0, &BatteryStatus::Discharging), "battery-level-60-symbolic");
+    }
+
+    #[test]
+    fn resolve_icon_adds_charging_infix_while_charging() {
+        let monitor = BatteryMonitor::new(Config::default());
+        assert_eq!(
+            monitor.resolve_icon(50.0, &BatteryStatus::Charging),
+            "battery-level-50-charging-symbolic"
+        );
+    }
+
+    #[test]
+    fn resolve_icon_falls_back_when_icon_list_empty() {
+        let mut config = Config::default();
+        config.format_icons = Vec::new();
+        let monitor = BatteryMonitor::new(config);
+        assert_eq!(monitor.resolve_icon(50.0, &BatteryStatus::Discharging), "battery-missing-symbolic");
+    }
+
+    fn emergency_config(countdown_secs: u64) -> Config {
+        let mut config = Config::default();
+        config.critical_action.action = Some(SystemAction::Suspend);
+        config.critical_action.emergency_threshold = 5.0;
+        config.critical_action.countdown_secs = countdown_secs;
+        config
+    }
+
+    fn battery_info(capacity: f64, status: BatteryStatus) -> BatteryInfo {
+        BatteryInfo { capacity, status, energy_now: 0.0, energy_full: 0.0, power_now: 0.0, batteries: Vec::new() }
+    }
+
+    #[test]
+    fn emergency_never_fires_while_plugged_or_unknown() {
+        // countdown_secs is irrelevant here: the status check short-circuits
+        // before the deadline/fire logic is ever touched.
+        let monitor = BatteryMonitor::new(emergency_config(9999));
+        for status in [BatteryStatus::Charging, BatteryStatus::Full, BatteryStatus::Unknown] {
+            let info = battery_info(1.0, status);
+            assert!(monitor.check_emergency_action(&info).is_none());
+        }
+    }
+
+    #[test]
+    fn reset_emergency_state_only_when_plugged() {
+        let monitor = BatteryMonitor::new(emergency_config(9999));
+        *monitor.emergency_fired.lock().unwrap() = true;
+        *monitor.emergency_deadline.lock().unwrap() = Some(Instant::now());
+
+        monitor.reset_emergency_state_if_plugged(&BatteryStatus::Discharging);
+        assert!(*monitor.emergency_fired.lock().unwrap());
+        assert!(monitor.emergency_deadline.lock().unwrap().is_some());
+
+        monitor.reset_emergency_state_if_plugged(&BatteryStatus::Charging);
+        assert!(!*monitor.emergency_fired.lock().unwrap());
+        assert!(monitor.emergency_deadline.lock().unwrap().is_none());
+    }
+
+    #[test]
+    fn emergency_deadline_is_armed_once_and_stable_across_polls() {
+        // countdown_secs is kept large so the deadline never elapses in
+        // this test: letting it reach zero would run the real
+        // systemctl suspend/hibernate/poweroff command via execute_command.
+        // What we're verifying here is the piece that makes "reach zero
+        // exactly once" possible at all — the deadline is armed on first
+        // observation and does NOT get pushed back on every later poll.
+        let monitor = BatteryMonitor::new(emergency_config(9999));
+        let info = battery_info(1.0, BatteryStatus::Discharging);
+
+        assert!(monitor.check_emergency_action(&info).is_some());
+        let first_deadline = *monitor.emergency_deadline.lock().unwrap();
+
+        assert!(monitor.check_emergency_action(&info).is_some());
+        let second_deadline = *monitor.emergency_deadline.lock().unwrap();
+
+        assert_eq!(first_deadline, second_deadline);
+        assert!(!*monitor.emergency_fired.lock().unwrap());
+    }
+
+    #[test]
+    fn emergency_fired_latch_blocks_further_firing() {
+        // Simulates "already fired" directly rather than letting the
+        // countdown elapse, so this doesn't invoke the real system action.
+        let monitor = BatteryMonitor::new(emergency_config(9999));
+        *monitor.emergency_fired.lock().unwrap() = true;
+
+        let info = battery_info(1.0, BatteryStatus::Discharging);
+        assert!(monitor.check_emergency_action(&info).is_none());
+    }
+}
@@ -13,7 +13,11 @@ pub struct Config {
     #[serde(default = "default_healthy")]
     pub healthy_threshold: f64,
     #[serde(default = "default_battery_path")]
-    pub battery_path: String,
+    pub battery_path: BatteryPath,
+    #[serde(default)]
+    pub show_battery_detail: bool,
+    #[serde(default)]
+    pub show_time_estimate: bool,
     #[serde(default = "default_poll_interval")]
     pub poll_interval_secs: u64,
     #[serde(default)]
@@ -22,6 +26,14 @@ pub struct Config {
     pub timeouts: TimeoutConfig,
     #[serde(default)]
     pub disable: Vec<String>,
+    #[serde(default)]
+    pub charge_limit: ChargeLimitConfig,
+    #[serde(default)]
+    pub format: FormatConfig,
+    #[serde(default = "default_format_icons")]
+    pub format_icons: Vec<String>,
+    #[serde(default)]
+    pub critical_action: CriticalActionConfig,
 }
 
 #[derive(Debug, Deserialize, Clone)]
@@ -38,6 +50,8 @@ pub struct TimeoutConfig {
     pub full: u64,
     #[serde(default = "default_timeout")]
     pub healthy: u64,
+    #[serde(default = "default_timeout")]
+    pub charge_limited: u64,
 }
 
 impl Default for TimeoutConfig {
@@ -49,6 +63,7 @@ impl Default for TimeoutConfig {
             low: default_timeout_critical(),
             full: default_timeout(),
             healthy: default_timeout(),
+            charge_limited: default_timeout(),
         }
     }
 }
@@ -96,6 +111,12 @@ pub struct CommandConfig {
     pub on_full: Option<String>,
     #[serde(default)]
     pub on_healthy: Option<String>,
+    #[serde(default)]
+    pub on_charge_limited: Option<String>,
+    #[serde(default)]
+    pub on_plugged: Option<String>,
+    #[serde(default)]
+    pub on_unplugged: Option<String>,
 }
 
 impl Default for CommandConfig {
@@ -107,10 +128,207 @@ impl Default for CommandConfig {
             on_low: None,
             on_full: None,
             on_healthy: None,
+            on_charge_limited: None,
+            on_plugged: None,
+            on_unplugged: None,
+        }
+    }
+}
+
+/// `battery_path` accepts either a single sysfs path, an explicit list for
+/// multi-battery laptops, or the literal string `"auto"` to discover every
+/// `/sys/class/power_supply/BAT*` node.
+#[derive(Debug, Deserialize, Clone)]
+#[serde(untagged)]
+pub enum BatteryPath {
+    Single(String),
+    Multiple(Vec<String>),
+}
+
+impl BatteryPath {
+    pub fn resolve(&self) -> Vec<String> {
+        match self {
+            BatteryPath::Single(path) if path == "auto" => discover_batteries(),
+            BatteryPath::Single(path) => vec![path.clone()],
+            BatteryPath::Multiple(paths) => paths.clone(),
         }
     }
 }
 
+fn discover_batteries() -> Vec<String> {
+    let mut paths = Vec::new();
+    if let Ok(entries) = std::fs::read_dir("/sys/class/power_supply") {
+        for entry in entries.flatten() {
+            if let Some(name) = entry.file_name().to_str() {
+                if name.starts_with("BAT") {
+                    paths.push(format!("/sys/class/power_supply/{}", name));
+                }
+            }
+        }
+    }
+    paths.sort();
+    paths
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct ChargeLimitConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    #[serde(default = "default_charge_limit_start")]
+    pub start_percent: f64,
+    #[serde(default = "default_charge_limit_end")]
+    pub end_percent: f64,
+}
+
+impl Default for ChargeLimitConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            start_percent: default_charge_limit_start(),
+            end_percent: default_charge_limit_end(),
+        }
+    }
+}
+
+/// Per-level message templates. Supports `{capacity}`, `{status}`,
+/// `{time}`, `{icon}` and (for the `[critical_action]` ladder) `{action}`
+/// placeholders, mirroring waybar's `format`/`format-icons` design. A
+/// level whose template is empty (after trimming) suppresses the OSD
+/// entirely for that level, independent of the coarser `disable` list.
+#[derive(Debug, Deserialize, Clone)]
+pub struct FormatConfig {
+    #[serde(default = "default_format_healthy")]
+    pub healthy: String,
+    #[serde(default = "default_format_charging")]
+    pub charging: String,
+    #[serde(default = "default_format_critical")]
+    pub critical: String,
+    #[serde(default = "default_format_low")]
+    pub low: String,
+    #[serde(default = "default_format_normal")]
+    pub normal: String,
+    #[serde(default = "default_format_full")]
+    pub full: String,
+    #[serde(default = "default_format_charge_limited")]
+    pub charge_limited: String,
+    /// Shown while the `critical_action` countdown is ticking. `{time}` is
+    /// the remaining whole seconds and `{action}` is the configured
+    /// `suspend`/`hibernate`/`poweroff` action name.
+    #[serde(default = "default_format_emergency_countdown")]
+    pub emergency_countdown: String,
+    /// Shown once the countdown reaches zero and the action is executed.
+    #[serde(default = "default_format_emergency_fired")]
+    pub emergency_fired: String,
+    /// Shown once, the first time `apply_charge_limit` fails to write the
+    /// sysfs threshold nodes (e.g. running unprivileged).
+    #[serde(default = "default_format_charge_limit_warning")]
+    pub charge_limit_warning: String,
+}
+
+impl Default for FormatConfig {
+    fn default() -> Self {
+        Self {
+            healthy: default_format_healthy(),
+            charging: default_format_charging(),
+            critical: default_format_critical(),
+            low: default_format_low(),
+            normal: default_format_normal(),
+            full: default_format_full(),
+            charge_limited: default_format_charge_limited(),
+            emergency_countdown: default_format_emergency_countdown(),
+            emergency_fired: default_format_emergency_fired(),
+            charge_limit_warning: default_format_charge_limit_warning(),
+        }
+    }
+}
+
+fn default_format_healthy() -> String { "Healthy {capacity}%".to_string() }
+fn default_format_charging() -> String { "Charging {capacity}%".to_string() }
+fn default_format_critical() -> String { "Critical {capacity}%".to_string() }
+fn default_format_low() -> String { "Low {capacity}%".to_string() }
+fn default_format_normal() -> String { "Discharging {capacity}%".to_string() }
+fn default_format_full() -> String { "Full {capacity}%".to_string() }
+fn default_format_charge_limited() -> String { "Charge Limited {capacity}%".to_string() }
+fn default_format_emergency_countdown() -> String { "Critical {capacity}%: {action} in {time}s (plug in to cancel)".to_string() }
+fn default_format_emergency_fired() -> String { "Battery critical \u{2014} executing {action}".to_string() }
+fn default_format_charge_limit_warning() -> String { "Charge limit unavailable (permission denied?)".to_string() }
+
+/// Icon names bucketed evenly across 0-100% capacity. A `-charging`
+/// suffix (e.g. `battery-level-50-symbolic` -> `battery-level-50-charging-symbolic`)
+/// is applied automatically while the battery is charging.
+fn default_format_icons() -> Vec<String> {
+    vec![
+        "battery-level-0-symbolic".to_string(),
+        "battery-level-10-symbolic".to_string(),
+        "battery-level-20-symbolic".to_string(),
+        "battery-level-30-symbolic".to_string(),
+        "battery-level-40-symbolic".to_string(),
+        "battery-level-50-symbolic".to_string(),
+        "battery-level-60-symbolic".to_string(),
+        "battery-level-70-symbolic".to_string(),
+        "battery-level-80-symbolic".to_string(),
+        "battery-level-90-symbolic".to_string(),
+        "battery-level-100-symbolic".to_string(),
+    ]
+}
+
+/// The system action fired once the battery reaches `emergency_threshold`
+/// while discharging. Only armed once the user sets one explicitly, so an
+/// unconfigured `[critical_action]` section is a no-op.
+#[derive(Debug, Deserialize, Clone, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum SystemAction {
+    Suspend,
+    Hibernate,
+    Poweroff,
+}
+
+impl SystemAction {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SystemAction::Suspend => "suspend",
+            SystemAction::Hibernate => "hibernate",
+            SystemAction::Poweroff => "poweroff",
+        }
+    }
+
+    pub fn systemctl_command(&self) -> &'static str {
+        match self {
+            SystemAction::Suspend => "systemctl suspend",
+            SystemAction::Hibernate => "systemctl hibernate",
+            SystemAction::Poweroff => "systemctl poweroff",
+        }
+    }
+}
+
+/// Escalation ladder for critical battery levels, inspired by PumoPM's
+/// staged response: `low_threshold`/`critical_threshold` already show the
+/// regular OSD, and this adds a final `emergency_threshold` rung that runs
+/// a cancellable countdown and then a system action. Opt-in: `action`
+/// defaults to `None`, which disables the whole ladder.
+#[derive(Debug, Deserialize, Clone)]
+pub struct CriticalActionConfig {
+    #[serde(default = "default_emergency_threshold")]
+    pub emergency_threshold: f64,
+    #[serde(default = "default_countdown_secs")]
+    pub countdown_secs: u64,
+    #[serde(default)]
+    pub action: Option<SystemAction>,
+}
+
+impl Default for CriticalActionConfig {
+    fn default() -> Self {
+        Self {
+            emergency_threshold: default_emergency_threshold(),
+            countdown_secs: default_countdown_secs(),
+            action: None,
+        }
+    }
+}
+
+fn default_emergency_threshold() -> f64 { 5.0 }
+fn default_countdown_secs() -> u64 { 15 }
+
 fn default_timeout() -> u64 { 3000 }
 fn default_timeout_critical() -> u64 { 12000 }
 fn default_horizontal() -> String { "center".to_string() }
@@ -118,8 +336,13 @@ fn default_vertical() -> String { "top".to_string() }
 fn default_critical() -> f64 { 10.0 }
 fn default_low() -> f64 { 20.0 }
 fn default_healthy() -> f64 { 80.0 }
-fn default_battery_path() -> String { "/sys/class/power_supply/BAT0".to_string() }
-fn default_poll_interval() -> u64 { 5 }
+fn default_battery_path() -> BatteryPath { BatteryPath::Single("/sys/class/power_supply/BAT0".to_string()) }
+/// Purely a safety net for drivers that never emit a `uevent` on change;
+/// inotify (see `watch_battery_events` in `main.rs`) handles the real-time
+/// path, so this only needs to be a coarse backstop.
+fn default_poll_interval() -> u64 { 60 }
+fn default_charge_limit_start() -> f64 { 75.0 }
+fn default_charge_limit_end() -> f64 { 80.0 }
 
 impl Default for Config {
     fn default() -> Self {
@@ -129,10 +352,16 @@ impl Default for Config {
             low_threshold: default_low(),
             healthy_threshold: default_healthy(),
             battery_path: default_battery_path(),
+            show_battery_detail: false,
+            show_time_estimate: false,
             poll_interval_secs: default_poll_interval(),
             commands: CommandConfig::default(),
             timeouts: TimeoutConfig::default(),
             disable: Vec::new(),
+            charge_limit: ChargeLimitConfig::default(),
+            format: FormatConfig::default(),
+            format_icons: default_format_icons(),
+            critical_action: CriticalActionConfig::default(),
         }
     }
 }
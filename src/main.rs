@@ -1,6 +1,8 @@
 use anyhow::Result;
 use gtk4::prelude::*;
 use gtk4::{glib, Application};
+use inotify::{Inotify, WatchMask};
+use std::os::unix::io::AsRawFd;
 
 mod types;
 mod config;
@@ -11,9 +13,58 @@ use config::{load_config, load_css};
 use battery::BatteryMonitor;
 use ui::OSDWindow;
 
+/// Watches each battery's `uevent` node so `check_battery` can react the
+/// instant the kernel reports a plug/unplug or threshold crossing, instead
+/// of waiting for the next poll tick. A single bad/stale path (e.g. a
+/// `uevent` node that no longer exists) is logged and skipped rather than
+/// discarding the watches already added for the other, working paths.
+fn watch_battery_events(paths: &[String]) -> Result<Inotify> {
+    let mut inotify = Inotify::init()?;
+    for path in paths {
+        let uevent_path = format!("{}/uevent", path);
+        if let Err(e) = inotify.add_watch(&uevent_path, WatchMask::MODIFY) {
+            eprintln!("Failed to watch {}: {}", uevent_path, e);
+        }
+    }
+    Ok(inotify)
+}
+
+/// Ticks `check_battery` once a second while the `critical_action` ladder's
+/// countdown is on screen, so the remaining-seconds text actually counts
+/// down instead of only updating on the next uevent or poll tick. Stops as
+/// soon as a tick reports anything other than the "emergency" level (the
+/// countdown was cancelled by plugging in, or the action already fired).
+///
+/// Callers must have already won `monitor.try_start_countdown()` before
+/// calling this: `check_battery` is also invoked independently by the
+/// inotify watch and the poll-interval fallback, so without that guard
+/// either of those could spawn another one-second ticker on top of this
+/// one while the countdown is active.
+fn start_emergency_countdown(monitor: BatteryMonitor, show_result: impl Fn(String, String, String, u64) -> bool + 'static) {
+    glib::timeout_add_seconds_local(1, move || match monitor.check_battery() {
+        Ok(Some((icon, message, level, timeout))) => {
+            if show_result(icon, message, level, timeout) {
+                glib::ControlFlow::Continue
+            } else {
+                monitor.end_countdown();
+                glib::ControlFlow::Break
+            }
+        }
+        Ok(None) => {
+            monitor.end_countdown();
+            glib::ControlFlow::Break
+        }
+        Err(e) => {
+            eprintln!("Error checking battery: {}", e);
+            monitor.end_countdown();
+            glib::ControlFlow::Break
+        }
+    });
+}
+
 fn main() -> Result<()> {
     let config = load_config();
-    
+
     let app = Application::builder()
         .application_id("com.github.battery-osd")
         .build();
@@ -27,12 +78,18 @@ fn main() -> Result<()> {
         let osd = OSDWindow::new(app, &config);
         let monitor = BatteryMonitor::new(config.clone());
 
-        let poll_interval = config.poll_interval_secs;
+        if let Err(e) = monitor.apply_charge_limit() {
+            eprintln!("Failed to apply charge limit on startup: {}", e);
+        }
 
-        glib::timeout_add_seconds_local(poll_interval as u32, move || {
-            match monitor.check_battery() {
-                Ok(Some((icon, message, level, timeout))) => {
-                    osd.show_message(&icon, &message, &level);
+        // Renders a check_battery result and reports whether the
+        // critical_action countdown ("emergency" level) is still active, so
+        // callers know whether to keep ticking.
+        let show_result = {
+            let osd = osd.clone();
+            move |icon: String, message: String, level: String, timeout: u64| {
+                osd.show_message(&icon, &message, &level);
+                if timeout > 0 {
                     glib::timeout_add_local_once(
                         std::time::Duration::from_millis(timeout),
                         {
@@ -41,11 +98,49 @@ fn main() -> Result<()> {
                         }
                     );
                 }
+                level == "emergency"
+            }
+        };
+
+        let run_check = {
+            let monitor = monitor.clone();
+            let show_result = show_result.clone();
+            move || match monitor.check_battery() {
+                Ok(Some((icon, message, level, timeout))) => {
+                    if show_result(icon, message, level, timeout) && monitor.try_start_countdown() {
+                        start_emergency_countdown(monitor.clone(), show_result.clone());
+                    }
+                }
                 Ok(None) => {}
                 Err(e) => {
                     eprintln!("Error checking battery: {}", e);
                 }
             }
+        };
+
+        let paths = config.battery_path.resolve();
+        match watch_battery_events(&paths) {
+            Ok(mut inotify) => {
+                let fd = inotify.as_raw_fd();
+                let run_check_fd = run_check.clone();
+                glib::source::unix_fd_add_local(fd, glib::IOCondition::IN, move |_, _| {
+                    let mut buffer = [0u8; 4096];
+                    if let Err(e) = inotify.read_events(&mut buffer) {
+                        eprintln!("Error reading inotify events: {}", e);
+                    }
+                    run_check_fd();
+                    glib::ControlFlow::Continue
+                });
+            }
+            Err(e) => {
+                eprintln!("Failed to watch battery uevent nodes, relying on polling only: {}", e);
+            }
+        }
+
+        // Safety net for drivers that never emit a uevent on change.
+        let poll_interval = config.poll_interval_secs;
+        glib::timeout_add_seconds_local(poll_interval as u32, move || {
+            run_check();
             glib::ControlFlow::Continue
         });
     });
@@ -6,8 +6,33 @@ pub enum BatteryStatus {
     Unknown,
 }
 
+/// Readings for one `/sys/class/power_supply/BATx` node.
+#[derive(Debug, Clone)]
+pub struct SingleBattery {
+    pub name: String,
+    pub capacity: f64,
+    pub status: BatteryStatus,
+    /// Full-charge capacity, used to weight this pack when aggregating
+    /// multiple batteries. Falls back to `1.0` (equal weighting) when the
+    /// kernel only exposes a plain `capacity` percentage.
+    pub energy_full: f64,
+    /// Remaining capacity, in the same unit as `energy_full`. `0.0` when
+    /// unavailable.
+    pub energy_now: f64,
+    /// Instantaneous charge/discharge rate, in the same unit basis as
+    /// `energy_full`/`energy_now` (so `energy_now / power_now` is hours).
+    /// `0.0` when the kernel doesn't expose a rate node.
+    pub power_now: f64,
+}
+
+/// Aggregate view across one or more batteries, as returned to
+/// `BatteryMonitor`.
 #[derive(Debug, Clone)]
 pub struct BatteryInfo {
     pub capacity: f64,
     pub status: BatteryStatus,
+    pub energy_now: f64,
+    pub energy_full: f64,
+    pub power_now: f64,
+    pub batteries: Vec<SingleBattery>,
 }
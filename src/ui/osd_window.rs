@@ -95,6 +95,9 @@ impl OSDWindow {
         self.window.remove_css_class("full");
         self.window.remove_css_class("healthy");
         self.window.remove_css_class("normal");
+        self.window.remove_css_class("charge_limited");
+        self.window.remove_css_class("warning");
+        self.window.remove_css_class("emergency");
         self.window.add_css_class(level);
         self.window.set_visible(true);
     }